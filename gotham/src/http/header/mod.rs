@@ -0,0 +1,338 @@
+//! Custom typed headers used by Gotham to apply sensible, RFC-conformant defaults to HTTP
+//! responses.
+
+use std::fmt;
+use std::str;
+use std::str::FromStr;
+
+use hyper::Error;
+use hyper::header::{Formatter, Header, Raw};
+
+/// The `X-Request-Id` header, carrying the unique id Gotham assigns to every request so that it
+/// can be correlated across logs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XRequestId(pub String);
+
+impl Header for XRequestId {
+    fn header_name() -> &'static str {
+        "X-Request-Id"
+    }
+
+    fn parse_header(raw: &Raw) -> Result<Self, Error> {
+        raw.one()
+            .and_then(|line| str::from_utf8(line).ok())
+            .map(|s| XRequestId(s.to_owned()))
+            .ok_or(Error::Header)
+    }
+
+    fn fmt_header(&self, f: &mut Formatter) -> fmt::Result {
+        f.fmt_line(&self.0)
+    }
+}
+
+/// The `X-Frame-Options` header, controlling whether a page may be rendered inside a
+/// `frame`/`iframe`/`object`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XFrameOptions {
+    /// `DENY` — the page may not be framed, regardless of the framing site.
+    Deny,
+    /// `SAMEORIGIN` — the page may only be framed by a page of the same origin.
+    SameOrigin,
+}
+
+impl fmt::Display for XFrameOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            XFrameOptions::Deny => "DENY",
+            XFrameOptions::SameOrigin => "SAMEORIGIN",
+        })
+    }
+}
+
+impl FromStr for XFrameOptions {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "DENY" => Ok(XFrameOptions::Deny),
+            "SAMEORIGIN" => Ok(XFrameOptions::SameOrigin),
+            _ => Err(Error::Header),
+        }
+    }
+}
+
+impl Header for XFrameOptions {
+    fn header_name() -> &'static str {
+        "X-Frame-Options"
+    }
+
+    fn parse_header(raw: &Raw) -> Result<Self, Error> {
+        raw.one()
+            .and_then(|line| str::from_utf8(line).ok())
+            .and_then(|s| s.parse().ok())
+            .ok_or(Error::Header)
+    }
+
+    fn fmt_header(&self, f: &mut Formatter) -> fmt::Result {
+        f.fmt_line(self)
+    }
+}
+
+/// The `X-XSS-Protection` header, controlling the browser's built-in reflected XSS filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XXssProtection {
+    /// `0` — the filter is disabled.
+    Disable,
+    /// `1; mode=block` — the filter is enabled, and the browser blocks rendering of the page
+    /// entirely when an attack is detected, rather than sanitizing the response.
+    EnableBlock,
+}
+
+impl fmt::Display for XXssProtection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            XXssProtection::Disable => "0",
+            XXssProtection::EnableBlock => "1; mode=block",
+        })
+    }
+}
+
+impl Header for XXssProtection {
+    fn header_name() -> &'static str {
+        "X-XSS-Protection"
+    }
+
+    fn parse_header(raw: &Raw) -> Result<Self, Error> {
+        raw.one()
+            .and_then(|line| str::from_utf8(line).ok())
+            .and_then(|s| match s {
+                "0" => Some(XXssProtection::Disable),
+                "1; mode=block" => Some(XXssProtection::EnableBlock),
+                _ => None,
+            })
+            .ok_or(Error::Header)
+    }
+
+    fn fmt_header(&self, f: &mut Formatter) -> fmt::Result {
+        f.fmt_line(self)
+    }
+}
+
+/// The `X-Content-Type-Options` header, preventing browsers from MIME-sniffing a response away
+/// from the declared `Content-Type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XContentTypeOptions {
+    /// `nosniff` — the only defined value.
+    NoSniff,
+}
+
+impl fmt::Display for XContentTypeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("nosniff")
+    }
+}
+
+impl Header for XContentTypeOptions {
+    fn header_name() -> &'static str {
+        "X-Content-Type-Options"
+    }
+
+    fn parse_header(raw: &Raw) -> Result<Self, Error> {
+        raw.one()
+            .and_then(|line| str::from_utf8(line).ok())
+            .and_then(|s| match s {
+                "nosniff" => Some(XContentTypeOptions::NoSniff),
+                _ => None,
+            })
+            .ok_or(Error::Header)
+    }
+
+    fn fmt_header(&self, f: &mut Formatter) -> fmt::Result {
+        f.fmt_line(self)
+    }
+}
+
+/// The `Content-Security-Policy` header, carrying a directive string that restricts the sources
+/// a page may load content from. Gotham treats the policy as an opaque string; it does not parse
+/// or validate individual directives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentSecurityPolicy(pub String);
+
+impl Header for ContentSecurityPolicy {
+    fn header_name() -> &'static str {
+        "Content-Security-Policy"
+    }
+
+    fn parse_header(raw: &Raw) -> Result<Self, Error> {
+        raw.one()
+            .and_then(|line| str::from_utf8(line).ok())
+            .map(|s| ContentSecurityPolicy(s.to_owned()))
+            .ok_or(Error::Header)
+    }
+
+    fn fmt_header(&self, f: &mut Formatter) -> fmt::Result {
+        f.fmt_line(&self.0)
+    }
+}
+
+/// The `Strict-Transport-Security` header, instructing browsers to only ever contact the origin
+/// over HTTPS for `max_age` seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StrictTransportSecurity {
+    /// The number of seconds the HSTS policy should be cached for.
+    pub max_age: u64,
+    /// Whether the policy also applies to all subdomains of the origin.
+    pub include_subdomains: bool,
+    /// Whether to request inclusion in browser HSTS preload lists.
+    pub preload: bool,
+}
+
+impl StrictTransportSecurity {
+    /// Creates a `Strict-Transport-Security` policy with the given `max_age`, and
+    /// `includeSubDomains`/`preload` disabled.
+    pub fn new(max_age: u64) -> Self {
+        StrictTransportSecurity {
+            max_age,
+            include_subdomains: false,
+            preload: false,
+        }
+    }
+
+    /// Enables `includeSubDomains` on this policy.
+    pub fn include_subdomains(mut self) -> Self {
+        self.include_subdomains = true;
+        self
+    }
+
+    /// Enables `preload` on this policy.
+    pub fn preload(mut self) -> Self {
+        self.preload = true;
+        self
+    }
+}
+
+impl fmt::Display for StrictTransportSecurity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "max-age={}", self.max_age)?;
+
+        if self.include_subdomains {
+            f.write_str("; includeSubDomains")?;
+        }
+
+        if self.preload {
+            f.write_str("; preload")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Header for StrictTransportSecurity {
+    fn header_name() -> &'static str {
+        "Strict-Transport-Security"
+    }
+
+    fn parse_header(raw: &Raw) -> Result<Self, Error> {
+        let line = raw.one().and_then(|line| str::from_utf8(line).ok()).ok_or(
+            Error::Header,
+        )?;
+
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        let mut preload = false;
+
+        for part in line.split(';').map(|part| part.trim()) {
+            if part.starts_with("max-age=") {
+                if let Ok(value) = part["max-age=".len()..].parse() {
+                    max_age = Some(value);
+                    continue;
+                }
+            }
+
+            match part {
+                "includeSubDomains" => include_subdomains = true,
+                "preload" => preload = true,
+                _ => (),
+            }
+        }
+
+        match max_age {
+            Some(max_age) => Ok(StrictTransportSecurity {
+                max_age,
+                include_subdomains,
+                preload,
+            }),
+            None => Err(Error::Header),
+        }
+    }
+
+    fn fmt_header(&self, f: &mut Formatter) -> fmt::Result {
+        f.fmt_line(self)
+    }
+}
+
+/// The `Referrer-Policy` header, controlling how much referrer information is included with
+/// requests made from a page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferrerPolicy {
+    /// `no-referrer`
+    NoReferrer,
+    /// `no-referrer-when-downgrade`
+    NoReferrerWhenDowngrade,
+    /// `origin`
+    Origin,
+    /// `origin-when-cross-origin`
+    OriginWhenCrossOrigin,
+    /// `same-origin`
+    SameOrigin,
+    /// `strict-origin`
+    StrictOrigin,
+    /// `strict-origin-when-cross-origin`
+    StrictOriginWhenCrossOrigin,
+    /// `unsafe-url`
+    UnsafeUrl,
+}
+
+impl fmt::Display for ReferrerPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ReferrerPolicy::NoReferrer => "no-referrer",
+            ReferrerPolicy::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+            ReferrerPolicy::Origin => "origin",
+            ReferrerPolicy::OriginWhenCrossOrigin => "origin-when-cross-origin",
+            ReferrerPolicy::SameOrigin => "same-origin",
+            ReferrerPolicy::StrictOrigin => "strict-origin",
+            ReferrerPolicy::StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+            ReferrerPolicy::UnsafeUrl => "unsafe-url",
+        })
+    }
+}
+
+impl Header for ReferrerPolicy {
+    fn header_name() -> &'static str {
+        "Referrer-Policy"
+    }
+
+    fn parse_header(raw: &Raw) -> Result<Self, Error> {
+        raw.one()
+            .and_then(|line| str::from_utf8(line).ok())
+            .and_then(|s| match s {
+                "no-referrer" => Some(ReferrerPolicy::NoReferrer),
+                "no-referrer-when-downgrade" => Some(ReferrerPolicy::NoReferrerWhenDowngrade),
+                "origin" => Some(ReferrerPolicy::Origin),
+                "origin-when-cross-origin" => Some(ReferrerPolicy::OriginWhenCrossOrigin),
+                "same-origin" => Some(ReferrerPolicy::SameOrigin),
+                "strict-origin" => Some(ReferrerPolicy::StrictOrigin),
+                "strict-origin-when-cross-origin" => {
+                    Some(ReferrerPolicy::StrictOriginWhenCrossOrigin)
+                }
+                "unsafe-url" => Some(ReferrerPolicy::UnsafeUrl),
+                _ => None,
+            })
+            .ok_or(Error::Header)
+    }
+
+    fn fmt_header(&self, f: &mut Formatter) -> fmt::Result {
+        f.fmt_line(self)
+    }
+}
@@ -0,0 +1,4 @@
+//! Helpers for working with HTTP requests and responses.
+
+pub mod header;
+pub mod response;
@@ -1,14 +1,39 @@
 //! Helpers for HTTP response generation
 
+use std::io::Write;
+use std::str;
+
+use flate2::Compression;
+use flate2::write::{DeflateEncoder, GzEncoder};
 use hyper::{Method, Response, StatusCode};
-use hyper::header::{ContentLength, ContentType};
+use hyper::header::{AcceptEncoding, ContentEncoding, ContentLength, ContentType, Encoding,
+                     EntityTag, ETag, Headers, HttpDate, IfModifiedSince, IfNoneMatch,
+                     LastModified, Location, Quality, Vary};
 use mime::Mime;
+use unicase::UniCase;
 
-use state::{request_id, FromState, State};
-use http::header::{XContentTypeOptions, XFrameOptions, XRequestId, XXssProtection};
+use state::{request_id, FromState, State, StateData};
+use http::header::{ContentSecurityPolicy, ReferrerPolicy, StrictTransportSecurity,
+                    XContentTypeOptions, XFrameOptions, XRequestId, XXssProtection};
 
 type Body = (Vec<u8>, Mime);
 
+/// A content coding that this module knows how to apply to a response body.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ContentCoding {
+    Gzip,
+    Deflate,
+}
+
+impl From<ContentCoding> for Encoding {
+    fn from(coding: ContentCoding) -> Encoding {
+        match coding {
+            ContentCoding::Gzip => Encoding::Gzip,
+            ContentCoding::Deflate => Encoding::Deflate,
+        }
+    }
+}
+
 /// Creates a `Response` object and populates it with a set of default headers that help to improve
 /// security and conformance to best practice.
 ///
@@ -140,7 +165,7 @@ pub fn extend_response(state: &State, res: &mut Response, status: StatusCode, bo
 
     match body {
         Some((body, mime)) => {
-            set_headers(state, res, Some(mime), Some(body.len() as u64));
+            set_headers(state, res, Some(mime), Some(&body));
             res.set_status(status);
 
             match *Method::borrow_from(state) {
@@ -155,9 +180,491 @@ pub fn extend_response(state: &State, res: &mut Response, status: StatusCode, bo
     };
 }
 
+/// Creates a `Response` object in the same way as `create_response`, but transparently
+/// compresses the body when the request's `Accept-Encoding` header indicates that the client
+/// supports a coding this crate knows how to produce.
+///
+/// The inbound `Accept-Encoding` header is parsed as a list of `coding;q=<value>` tokens; any
+/// entry with `q=0` is discarded, a missing `q` defaults to `1.0`, and the highest-quality
+/// supported coding (`gzip` or `deflate`) is selected. `identity` and unsupported codings are
+/// treated as "do not compress" and fall back to the behaviour of `create_response`, as does a
+/// request with no `Accept-Encoding` header at all. Compression is also skipped when the body is
+/// empty, and for `HEAD` requests whose body is dropped before it would ever be compressed.
+///
+/// When a coding is selected, the response gains a `Content-Encoding` header naming it, a
+/// `Vary: Accept-Encoding` header, and a `Content-Length` recomputed from the compressed body.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Response, StatusCode};
+/// # use hyper::header::{AcceptEncoding, ContentEncoding, Encoding, qitem};
+/// # use gotham::state::State;
+/// # use gotham::http::response::create_compressed_response;
+/// # use gotham::test::TestServer;
+/// #
+/// static BODY: &'static [u8] = b"Hello, world! Hello, world! Hello, world!";
+///
+/// fn handler(state: State) -> (State, Response) {
+///     let response = create_compressed_response(
+///         &state,
+///         StatusCode::Ok,
+///         Some((BODY.to_vec(), mime::TEXT_PLAIN)),
+///     );
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .header(AcceptEncoding(vec![qitem(Encoding::Gzip)]))
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::Ok);
+/// #     assert_eq!(
+/// #         *response.headers().get::<ContentEncoding>().unwrap(),
+/// #         ContentEncoding(vec![Encoding::Gzip])
+/// #     );
+/// # }
+/// ```
+pub fn create_compressed_response(state: &State, status: StatusCode, body: Option<Body>) -> Response {
+    let mut res = Response::new();
+
+    let coding = body.as_ref().and_then(|&(ref body, _)| {
+        if body.is_empty() {
+            return None;
+        }
+
+        match *Method::borrow_from(state) {
+            Method::Head => None,
+            _ => negotiate_content_coding(state),
+        }
+    });
+
+    match (body, coding) {
+        (Some((body, mime)), Some(coding)) => {
+            let compressed = compress_body(coding, &body);
+
+            set_headers(state, &mut res, Some(mime), Some(&compressed));
+            set_content_coding_headers(&mut res, coding);
+            res.set_status(status);
+            res.set_body(compressed);
+        }
+        (body, _) => extend_response(state, &mut res, status, body),
+    };
+
+    res
+}
+
+/// Parses the `Accept-Encoding` header off `state` and selects the highest-quality content
+/// coding that this module can produce, or `None` if the client's preferences can't be met with
+/// a coding we support. A `*` entry is treated as accepting gzip, our default coding, at its
+/// given quality.
+fn negotiate_content_coding(state: &State) -> Option<ContentCoding> {
+    let headers = Headers::borrow_from(state);
+
+    let accept_encoding = match headers.get::<AcceptEncoding>() {
+        Some(accept_encoding) => accept_encoding,
+        None => return None,
+    };
+
+    accept_encoding
+        .iter()
+        .filter(|qitem| qitem.quality > Quality(0))
+        .filter_map(|qitem| {
+            let coding = match qitem.item {
+                Encoding::Gzip => Some(ContentCoding::Gzip),
+                Encoding::Deflate => Some(ContentCoding::Deflate),
+                Encoding::EncodingExt(ref ext) if ext == "*" => Some(ContentCoding::Gzip),
+                _ => None,
+            };
+
+            coding.map(|coding| (coding, qitem.quality))
+        })
+        .max_by_key(|&(_, quality)| quality)
+        .map(|(coding, _)| coding)
+}
+
+/// Compresses `body` with the given `ContentCoding`.
+fn compress_body(coding: ContentCoding, body: &[u8]) -> Vec<u8> {
+    match coding {
+        ContentCoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).expect("gzip compression should not fail");
+            encoder.finish().expect("gzip compression should not fail")
+        }
+        ContentCoding::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).expect(
+                "deflate compression should not fail",
+            );
+            encoder.finish().expect("deflate compression should not fail")
+        }
+    }
+}
+
+/// Sets the `Content-Encoding` and `Vary` headers that accompany a compressed body.
+fn set_content_coding_headers(res: &mut Response, coding: ContentCoding) {
+    let headers = res.headers_mut();
+    headers.set(ContentEncoding(vec![coding.into()]));
+    headers.set(Vary::Items(vec![UniCase::new("accept-encoding".to_owned())]));
+}
+
+/// Creates a `Response` object in the same way as `create_response`, but supports cheap
+/// cache revalidation by computing a strong `ETag` for `body` and, when `last_modified` is
+/// supplied, a `Last-Modified` header alongside it.
+///
+/// Before the body is serialized, the request headers held in `state` are inspected for
+/// `If-None-Match` and `If-Modified-Since`. `If-None-Match` takes precedence when both are
+/// present: if it contains `*` or a tag that weakly matches the computed `ETag`, or if
+/// `If-Modified-Since` is at or after `last_modified`, the response is downgraded to
+/// `304 Not Modified` with the body and `Content-Length`/`Content-Type` dropped, while the
+/// `ETag` and `Last-Modified` validators are retained. This revalidation is only attempted for
+/// `GET`/`HEAD` requests.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Response, StatusCode};
+/// # use hyper::header::{ETag, EntityTag, IfNoneMatch};
+/// # use gotham::state::State;
+/// # use gotham::http::response::create_conditional_response;
+/// # use gotham::test::TestServer;
+/// #
+/// static BODY: &'static [u8] = b"Hello, world!";
+///
+/// fn handler(state: State) -> (State, Response) {
+///     let response = create_conditional_response(
+///         &state,
+///         StatusCode::Ok,
+///         Some((BODY.to_vec(), mime::TEXT_PLAIN)),
+///         None,
+///     );
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::Ok);
+/// #     let etag = response.headers().get::<ETag>().unwrap().clone();
+/// #
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .header(IfNoneMatch::Items(vec![etag.0]))
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::NotModified);
+/// # }
+/// ```
+pub fn create_conditional_response(
+    state: &State,
+    status: StatusCode,
+    body: Option<Body>,
+    last_modified: Option<HttpDate>,
+) -> Response {
+    let mut res = Response::new();
+
+    match body {
+        Some((body, mime)) => {
+            let etag = ETag(entity_tag_for(&body));
+
+            if is_not_modified(state, &etag, last_modified.as_ref()) {
+                set_headers(state, &mut res, None, None);
+                res.headers_mut().remove::<ContentType>();
+                res.headers_mut().remove::<ContentLength>();
+                res.headers_mut().set(etag);
+                if let Some(last_modified) = last_modified {
+                    res.headers_mut().set(LastModified(last_modified));
+                }
+                res.set_status(StatusCode::NotModified);
+            } else {
+                set_headers(state, &mut res, Some(mime), Some(&body));
+                res.headers_mut().set(etag);
+                if let Some(last_modified) = last_modified {
+                    res.headers_mut().set(LastModified(last_modified));
+                }
+                res.set_status(status);
+
+                match *Method::borrow_from(state) {
+                    Method::Head => (),
+                    _ => res.set_body(body),
+                }
+            }
+        }
+        None => extend_response(state, &mut res, status, None),
+    };
+
+    res
+}
+
+/// Determines whether the request held in `state` has already revalidated `etag`/
+/// `last_modified`, per the precedence rules of `If-None-Match` over `If-Modified-Since`.
+fn is_not_modified(state: &State, etag: &ETag, last_modified: Option<&HttpDate>) -> bool {
+    match *Method::borrow_from(state) {
+        Method::Get | Method::Head => (),
+        _ => return false,
+    }
+
+    let headers = Headers::borrow_from(state);
+
+    if let Some(if_none_match) = headers.get::<IfNoneMatch>() {
+        return match *if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(ref tags) => tags.iter().any(|tag| tag.weak_eq(&etag.0)),
+        };
+    }
+
+    if let Some(if_modified_since) = headers.get::<IfModifiedSince>() {
+        if let Some(last_modified) = last_modified {
+            return if_modified_since.0 >= *last_modified;
+        }
+    }
+
+    false
+}
+
+/// Computes a strong `ETag` entity tag from the FNV-1a hash of `body`.
+fn entity_tag_for(body: &[u8]) -> EntityTag {
+    EntityTag::strong(format!("{:016x}", fnv1a_hash(body)))
+}
+
+/// A simple, dependency-free FNV-1a hash, used to derive `ETag`s from response bodies.
+fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Creates a redirect `Response`, setting `Location` to `location` and populating the same
+/// security headers as `set_headers`.
+///
+/// `status` must be a redirection status code (one of `301`, `302`, `303`, `307`, `308`, or any
+/// other `3xx`); `create_redirect_response` panics otherwise, in the same way `extend_response`
+/// panics on an unrepresentable `Content-Length`. For all methods other than `HEAD`, a short HTML
+/// body pointing at `location` is generated and included, so that clients which don't follow
+/// redirects automatically still have something human-readable to show.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Response, StatusCode};
+/// # use hyper::header::Location;
+/// # use gotham::state::State;
+/// # use gotham::http::response::create_redirect_response;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response) {
+///     let response = create_redirect_response(&state, StatusCode::MovedPermanently, "/new-path");
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/old-path")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::MovedPermanently);
+/// #     assert_eq!(
+/// #         *response.headers().get::<Location>().unwrap(),
+/// #         Location::new("/new-path")
+/// #     );
+/// # }
+/// ```
+pub fn create_redirect_response(state: &State, status: StatusCode, location: &str) -> Response {
+    if !status.is_redirection() {
+        error!(
+            "[{}] {} is not a redirection status code",
+            request_id(state),
+            status
+        );
+        panic!(
+            "[{}] {} is not a redirection status code",
+            request_id(state),
+            status
+        );
+    }
+
+    let mut res = Response::new();
+
+    let body = match *Method::borrow_from(state) {
+        Method::Head => None,
+        _ => {
+            let escaped_location = escape_html(location);
+            Some((
+                format!(
+                    "<!DOCTYPE html><html><head><title>{status}</title></head><body>\
+                     <p>Redirecting to <a href=\"{location}\">{location}</a></p></body></html>",
+                    status = status,
+                    location = escaped_location
+                ).into_bytes(),
+                mime::TEXT_HTML,
+            ))
+        }
+    };
+
+    extend_response(state, &mut res, status, body);
+    res.headers_mut().set(Location::new(location.to_owned()));
+
+    res
+}
+
+/// Escapes the characters in `value` that are significant in HTML markup, so that it's safe to
+/// interpolate into a response body. Redirect targets are frequently influenced by user input
+/// (e.g. a `?next=` query parameter), so this is applied before `location` is embedded in the
+/// body generated by `create_redirect_response`.
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Configures the security headers that `set_headers` applies to a response.
+///
+/// The `Default` implementation reproduces Gotham's historical, fixed behaviour: `X-Frame-Options:
+/// DENY`, `X-XSS-Protection: 1; mode=block`, and `X-Content-Type-Options: nosniff`, with
+/// `Content-Security-Policy`, `Strict-Transport-Security` and `Referrer-Policy` left unset. An
+/// application can override this default by `put`-ing a customised `SecurityHeaders` into
+/// `State`; `set_headers` will pick it up via `FromState`. Each of the `X-Frame-Options`,
+/// `X-XSS-Protection` and `X-Content-Type-Options` headers can be disabled by passing `None` to
+/// its setter; `Content-Security-Policy`, `Strict-Transport-Security` and `Referrer-Policy` are
+/// only emitted once set, and their setters have no way to unset them again.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use gotham::http::response::SecurityHeaders;
+/// # use gotham::http::header::{ReferrerPolicy, StrictTransportSecurity, XFrameOptions};
+/// #
+/// # fn main() {
+/// let security_headers = SecurityHeaders::default()
+///     .with_x_frame_options(Some(XFrameOptions::SameOrigin))
+///     .with_content_security_policy("default-src 'self'")
+///     .with_strict_transport_security(StrictTransportSecurity::new(31_536_000).preload())
+///     .with_referrer_policy(ReferrerPolicy::NoReferrerWhenDowngrade);
+/// # let _ = security_headers;
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecurityHeaders {
+    x_frame_options: Option<XFrameOptions>,
+    x_xss_protection: Option<XXssProtection>,
+    x_content_type_options: Option<XContentTypeOptions>,
+    content_security_policy: Option<ContentSecurityPolicy>,
+    strict_transport_security: Option<StrictTransportSecurity>,
+    referrer_policy: Option<ReferrerPolicy>,
+}
+
+impl StateData for SecurityHeaders {}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        SecurityHeaders {
+            x_frame_options: Some(XFrameOptions::Deny),
+            x_xss_protection: Some(XXssProtection::EnableBlock),
+            x_content_type_options: Some(XContentTypeOptions::NoSniff),
+            content_security_policy: None,
+            strict_transport_security: None,
+            referrer_policy: None,
+        }
+    }
+}
+
+impl SecurityHeaders {
+    /// Sets the `X-Frame-Options` header, or disables it entirely with `None`.
+    pub fn with_x_frame_options(mut self, value: Option<XFrameOptions>) -> Self {
+        self.x_frame_options = value;
+        self
+    }
+
+    /// Sets the `X-XSS-Protection` header, or disables it entirely with `None`.
+    pub fn with_x_xss_protection(mut self, value: Option<XXssProtection>) -> Self {
+        self.x_xss_protection = value;
+        self
+    }
+
+    /// Sets the `X-Content-Type-Options` header, or disables it entirely with `None`.
+    pub fn with_x_content_type_options(mut self, value: Option<XContentTypeOptions>) -> Self {
+        self.x_content_type_options = value;
+        self
+    }
+
+    /// Sets the `Content-Security-Policy` header to the given directive string.
+    pub fn with_content_security_policy<S: Into<String>>(mut self, policy: S) -> Self {
+        self.content_security_policy = Some(ContentSecurityPolicy(policy.into()));
+        self
+    }
+
+    /// Sets the `Strict-Transport-Security` header.
+    pub fn with_strict_transport_security(mut self, value: StrictTransportSecurity) -> Self {
+        self.strict_transport_security = Some(value);
+        self
+    }
+
+    /// Sets the `Referrer-Policy` header.
+    pub fn with_referrer_policy(mut self, value: ReferrerPolicy) -> Self {
+        self.referrer_policy = Some(value);
+        self
+    }
+}
+
 /// Sets a number of default headers in a `Response` that ensure security and conformance to
 /// best practice.
 ///
+/// A request handler may customise which of these headers are emitted by `put`-ing a
+/// `SecurityHeaders` value into `State` before the response is built; see `SecurityHeaders` for
+/// details. When no `SecurityHeaders` is present, `set_headers` falls back to
+/// `SecurityHeaders::default()`, reproducing today's fixed behaviour.
+///
+/// When `mime` is `None` and `body` is non-empty, the `Content-Type` is not left unset; instead
+/// the leading bytes of `body` are sniffed for a handful of well-known signatures (PDF, GIF, PNG,
+/// JPEG, HTML/XML, or plain UTF-8 text), falling back to `application/octet-stream` when nothing
+/// matches. Since `set_headers` sets `X-Content-Type-Options: nosniff` by default, this ensures
+/// clients aren't left to guess at a type gotham could have determined server-side.
+///
 /// # Examples
 ///
 /// When `Content-Type` and `Content-Length` are not provided, only the security headers are set on
@@ -246,7 +753,7 @@ pub fn extend_response(state: &State, res: &mut Response, status: StatusCode, bo
 ///         &state,
 ///         &mut response,
 ///         Some(mime::TEXT_PLAIN),
-///         Some(BODY.len() as u64),
+///         Some(BODY),
 ///     );
 ///
 ///     (state, response)
@@ -296,11 +803,13 @@ pub fn extend_response(state: &State, res: &mut Response, status: StatusCode, bo
 /// # );
 /// # }
 /// ```
-pub fn set_headers(state: &State, res: &mut Response, mime: Option<Mime>, length: Option<u64>) {
+pub fn set_headers(state: &State, res: &mut Response, mime: Option<Mime>, body: Option<&[u8]>) {
+    let security_headers = SecurityHeaders::try_borrow_from(state).cloned().unwrap_or_default();
+    let mime = mime.or_else(|| body.and_then(sniff_mime));
     let headers = res.headers_mut();
 
-    match length {
-        Some(length) => headers.set(ContentLength(length)),
+    match body {
+        Some(body) => headers.set(ContentLength(body.len() as u64)),
         None => headers.set(ContentLength(0)),
     }
 
@@ -310,7 +819,93 @@ pub fn set_headers(state: &State, res: &mut Response, mime: Option<Mime>, length
     };
 
     headers.set(XRequestId(request_id(state).into()));
-    headers.set(XFrameOptions::Deny);
-    headers.set(XXssProtection::EnableBlock);
-    headers.set(XContentTypeOptions::NoSniff);
+
+    if let Some(x_frame_options) = security_headers.x_frame_options {
+        headers.set(x_frame_options);
+    }
+
+    if let Some(x_xss_protection) = security_headers.x_xss_protection {
+        headers.set(x_xss_protection);
+    }
+
+    if let Some(x_content_type_options) = security_headers.x_content_type_options {
+        headers.set(x_content_type_options);
+    }
+
+    if let Some(content_security_policy) = security_headers.content_security_policy {
+        headers.set(content_security_policy);
+    }
+
+    if let Some(strict_transport_security) = security_headers.strict_transport_security {
+        headers.set(strict_transport_security);
+    }
+
+    if let Some(referrer_policy) = security_headers.referrer_policy {
+        headers.set(referrer_policy);
+    }
+}
+
+/// The number of leading bytes of a body that `sniff_mime` inspects when classifying content.
+const SNIFF_WINDOW: usize = 512;
+
+/// Classifies `body` by its leading bytes, in the absence of an explicitly supplied MIME type.
+///
+/// Returns `None` when `body` is empty; an unrecognised non-empty body is classified as
+/// `application/octet-stream` rather than returning `None`, so that callers always get a type to
+/// set once a body is known to exist.
+fn sniff_mime(body: &[u8]) -> Option<Mime> {
+    if body.is_empty() {
+        return None;
+    }
+
+    let window = &body[..body.len().min(SNIFF_WINDOW)];
+
+    if window.starts_with(b"%PDF") {
+        return Some("application/pdf".parse().unwrap());
+    }
+
+    if window.starts_with(b"GIF87a") || window.starts_with(b"GIF89a") {
+        return Some(mime::IMAGE_GIF);
+    }
+
+    if window.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(mime::IMAGE_PNG);
+    }
+
+    if window.starts_with(b"\xFF\xD8\xFF") {
+        return Some(mime::IMAGE_JPEG);
+    }
+
+    let trimmed = window
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map(|start| &window[start..])
+        .unwrap_or(window);
+
+    if starts_with_ignore_ascii_case(trimmed, b"<?xml") {
+        return Some("application/xml".parse().unwrap());
+    }
+
+    if starts_with_ignore_ascii_case(trimmed, b"<html") {
+        return Some(mime::TEXT_HTML);
+    }
+
+    // An error here doesn't necessarily mean `window` isn't text: if the only problem is an
+    // incomplete sequence at the very end, that's just the sniff window cutting a multi-byte
+    // character in half, not invalid UTF-8.
+    let looks_like_text = match str::from_utf8(window) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && body.len() > SNIFF_WINDOW,
+    };
+
+    if looks_like_text {
+        return Some(mime::TEXT_PLAIN);
+    }
+
+    Some(mime::APPLICATION_OCTET_STREAM)
+}
+
+/// Case-insensitive variant of `[u8]::starts_with`, used for the HTML/XML magic-byte checks.
+fn starts_with_ignore_ascii_case(haystack: &[u8], prefix: &[u8]) -> bool {
+    haystack.len() >= prefix.len() && haystack[..prefix.len()].eq_ignore_ascii_case(prefix)
 }